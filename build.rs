@@ -13,68 +13,85 @@
 //! The compiler maintains its store of target information here:
 //! <https://github.com/rust-lang/rust/tree/be28b6235e64e0f662b96b710bf3af9de169215c/compiler/rustc_target/src/spec>
 //!
-//! That module is not easily extracted into something that can be loaded here,
-//! so we are replicating it through string matching on the target name until
-//! the `cfg(target_has_atomic)` flag stabilizes.
+//! On compilers that support it (Rust 1.60+), we read the stabilized
+//! `cfg(target_has_atomic)` / `cfg(target_has_atomic_load_store)` information
+//! directly from `CARGO_CFG_TARGET_HAS_ATOMIC` / `CARGO_CFG_TARGET_HAS_ATOMIC_LOAD_STORE`
+//! instead of replicating that table ourselves; see [`version`] and
+//! [`detect_widths_from_cfg`]. The hand-maintained string matching in
+//! [`detect::detect`] only runs as a fallback, for compilers that predate the
+//! stabilization and for the handful of targets (such as `riscv32imc` vs.
+//! `riscv32imac`) that remain indistinguishable through `cfg` alone. That
+//! pure decision table lives in its own module, `detect.rs`, rather than
+//! here, specifically so `tests/detect.rs` can include it too and actually
+//! exercise it under `cargo test` — a build script itself is never compiled
+//! as a test target.
+//!
+//! The CAS half of [`Atomics`] comes from `CARGO_CFG_TARGET_HAS_ATOMIC` on the
+//! `cfg` path and from [`detect::assume_cas_follows_width`] on the fallback
+//! path; on both, the hand-maintained table in [`detect::apply_cas_overrides`]
+//! is applied on top, regardless of compiler version.
+//!
+//! When the `unsafe-assume-single-core` feature is enabled, whatever gaps
+//! remain are filled in under the assumption that they can be emulated with an
+//! interrupt-masking critical section; see [`apply_single_core_polyfill`] and
+//! `src/critical_section.rs`.
+//!
+//! When the `portable-atomic` feature is enabled, gaps are instead filled in
+//! because the `portable_atomic` crate itself emulates them (by critical
+//! section on targets that need it, and by other means on hosted targets);
+//! see [`apply_portable_atomic_override`]. In that mode it sets every flag in
+//! `Atomics` unconditionally, rather than only filling in missing widths, so
+//! no `radium_missing_*` cfg is emitted at all. The `target_arch` exclusions
+//! written directly into the `64` arms of `has_atomic!`/`has_atomic_cas!`
+//! (32-bit ARM Android, MIPS, PowerPC) are not cfgs from this script and still
+//! apply, so those targets get no 64-bit `Radium` impl under this feature.
 //!
 //! Use `rustc --print target-list` to enumerate the full list of targets
 //! available.
 
-/// Collection of flags indicating whether the target processor supports atomic
-/// instructions for a certain width.
-#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
-struct Atomics {
-    /// Target supports 8-bit atomics
-    has_8: bool,
-    /// Target supports 16-bit atomics
-    has_16: bool,
-    /// Target supports 32-bit atomics
-    has_32: bool,
-    /// Target supports 64-bit atomics
-    has_64: bool,
-    /// Target supports word-width atomics
-    has_ptr: bool,
-}
+mod detect;
+mod version;
 
-impl Atomics {
-    const ALL: Self = Self {
-        has_8: true,
-        has_16: true,
-        has_32: true,
-        has_64: true,
-        has_ptr: true,
-    };
-    const NONE: Self = Self {
-        has_8: false,
-        has_16: false,
-        has_32: false,
-        has_64: false,
-        has_ptr: false,
-    };
-}
+use detect::Atomics;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut atomics = Atomics::ALL;
-
-    let target = std::env::var("TARGET")?;
-    // Add new target strings here with their atomic availability.
-    #[allow(clippy::match_single_binding, clippy::single_match)]
-    match &*target {
-        _ => {}
+    // Every `radium_*` cfg this script can emit, declared up front so rustc's
+    // `unexpected_cfgs` lint (on by default, and therefore fatal under `-D
+    // warnings`) doesn't flag the ones a given build doesn't end up setting.
+    for cfg in [
+        "radium_missing_8",
+        "radium_missing_16",
+        "radium_missing_32",
+        "radium_missing_64",
+        "radium_missing_ptr",
+        "radium_missing_cas_8",
+        "radium_missing_cas_16",
+        "radium_missing_cas_32",
+        "radium_missing_cas_64",
+        "radium_missing_cas_ptr",
+        "radium_atomic_polyfill_8",
+        "radium_atomic_polyfill_16",
+        "radium_atomic_polyfill_32",
+        "radium_atomic_polyfill_64",
+        "radium_atomic_polyfill_ptr",
+        "radium_atomic_polyfill_cas_8",
+        "radium_atomic_polyfill_cas_16",
+        "radium_atomic_polyfill_cas_32",
+        "radium_atomic_polyfill_cas_64",
+        "radium_atomic_polyfill_cas_ptr",
+        "radium_s_mode",
+        "radium_disable_fiq",
+    ] {
+        println!("cargo::rustc-check-cfg=cfg({cfg})");
     }
 
+    let target = std::env::var("TARGET")?;
     let arch = target.split('-').next().ok_or("Invalid target triple")?;
-    // Add new architecture sections here with their atomic availability.
-    #[allow(clippy::match_single_binding, clippy::single_match)]
-    match arch {
-        // "riscv32imc-unknown-none-elf" and "riscv32imac-unknown-none-elf" are
-        // both `target_arch = "riscv32", and have no `cfg`-discoverable
-        // distinction. As such, the non-atomic RISC-V target must be discovered
-        // here, rather than in the macro.
-        "riscv32i" | "riscv32imc" => atomics = Atomics::NONE,
-        "riscv32imac" => atomics.has_64 = false,
-        _ => {}
-    }
+
+    let mut atomics =
+        detect_widths_from_cfg(&target, arch).unwrap_or_else(|| detect::detect(&target, arch));
+    apply_single_core_polyfill(&mut atomics);
+    apply_portable_atomic_override(&mut atomics);
 
     // Target detection prints out flags indicating that the target does **NOT**
     // have an atomic instruction for the specified width. This flag is picked
@@ -96,5 +113,177 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("cargo:rustc-cfg=radium_missing_ptr");
     }
 
+    // Same idea, but for compare-and-swap: a target can have plain atomic
+    // load/store for a width while lacking the instruction a read-modify-write
+    // loop needs. This is picked up by the `has_atomic_cas!` macro.
+    if !atomics.cas_8 {
+        println!("cargo:rustc-cfg=radium_missing_cas_8");
+    }
+    if !atomics.cas_16 {
+        println!("cargo:rustc-cfg=radium_missing_cas_16");
+    }
+    if !atomics.cas_32 {
+        println!("cargo:rustc-cfg=radium_missing_cas_32");
+    }
+    if !atomics.cas_64 {
+        println!("cargo:rustc-cfg=radium_missing_cas_64");
+    }
+    if !atomics.cas_ptr {
+        println!("cargo:rustc-cfg=radium_missing_cas_ptr");
+    }
+
+    // Without these, a cached build can carry stale `radium_missing_*` cfgs
+    // forward after the user changes `--target` handling or RUSTFLAGS, since
+    // cargo has no other way to know this build script's output depends on
+    // them.
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=CARGO_ENCODED_RUSTFLAGS");
+    println!("cargo:rerun-if-env-changed=RUSTFLAGS");
+
     Ok(())
 }
+
+/// Populates width and CAS availability from the stabilized `cfg` family
+/// Cargo exposes to build scripts, when the active compiler is new enough to
+/// guarantee it exists, then narrows the CAS flags further with the same
+/// hand-maintained table [`detect::detect`] uses.
+///
+/// `target_has_atomic` and `target_has_atomic_load_store` are two different
+/// signals: the former is set only where the target has the full
+/// compare-and-swap (or equivalent read-modify-write) instruction for a
+/// width, while the latter is set wherever the width can merely be loaded and
+/// stored atomically, which `target_has_atomic` implies but does not equal.
+/// Reading only `CARGO_CFG_TARGET_HAS_ATOMIC` and deriving `has_*` from it
+/// would be wrong for a CAS-less-but-load/store-capable target
+/// that isn't also in [`detect::apply_cas_overrides`]'s hand-maintained
+/// table: its `has_*` would come back `false`, losing `has_atomic!`-gated
+/// load/store support it actually has. So `has_*` is read from
+/// `CARGO_CFG_TARGET_HAS_ATOMIC_LOAD_STORE` and `cas_*` from
+/// `CARGO_CFG_TARGET_HAS_ATOMIC` directly; [`detect::apply_cas_overrides`] is
+/// still consulted afterward as a safety net for any target `rustc` itself
+/// reports incorrectly.
+///
+/// Returns `None` on older compilers, for the `riscv32i`/`riscv32imc`/
+/// `riscv32imac` targets that [`detect::detect`] tells apart by name (or if
+/// either environment variable is missing for any other reason), so callers
+/// can fall back to [`detect::detect`].
+fn detect_widths_from_cfg(target: &str, arch: &str) -> Option<Atomics> {
+    let rustc = version::detect()?;
+    if !rustc.at_least(version::RustcVersion::TARGET_HAS_ATOMIC) {
+        return None;
+    }
+    if matches!(arch, "riscv32i" | "riscv32imc" | "riscv32imac") {
+        return None;
+    }
+    let load_store_widths = std::env::var("CARGO_CFG_TARGET_HAS_ATOMIC_LOAD_STORE").ok()?;
+    let cas_widths = std::env::var("CARGO_CFG_TARGET_HAS_ATOMIC").ok()?;
+
+    let mut atomics = Atomics::NONE;
+    for width in load_store_widths.split(',') {
+        match width {
+            "8" => atomics.has_8 = true,
+            "16" => atomics.has_16 = true,
+            "32" => atomics.has_32 = true,
+            "64" => atomics.has_64 = true,
+            "ptr" => atomics.has_ptr = true,
+            _ => {}
+        }
+    }
+    for width in cas_widths.split(',') {
+        match width {
+            "8" => atomics.cas_8 = true,
+            "16" => atomics.cas_16 = true,
+            "32" => atomics.cas_32 = true,
+            "64" => atomics.cas_64 = true,
+            "ptr" => atomics.cas_ptr = true,
+            _ => {}
+        }
+    }
+    detect::apply_cas_overrides(&mut atomics, target, arch);
+    Some(atomics)
+}
+
+/// Fills in any gap left by [`detect::apply_cas_overrides`] when the
+/// `unsafe-assume-single-core` feature is enabled, and records which widths
+/// were filled in so `src/critical_section.rs` knows which ones to back with
+/// an interrupt-masking critical section rather than a hardware instruction.
+///
+/// This is sound only on genuinely single-core targets: a critical section
+/// disables interrupts on the current core, which excludes concurrent access
+/// from other tasks on *that* core, but provides no exclusion against other
+/// cores. Enabling the feature on a multi-core target is a soundness bug in
+/// the consuming crate, not in `radium`.
+fn apply_single_core_polyfill(atomics: &mut Atomics) {
+    if std::env::var_os("CARGO_FEATURE_UNSAFE_ASSUME_SINGLE_CORE").is_none() {
+        return;
+    }
+
+    if !atomics.has_8 {
+        atomics.has_8 = true;
+        println!("cargo:rustc-cfg=radium_atomic_polyfill_8");
+    }
+    if !atomics.has_16 {
+        atomics.has_16 = true;
+        println!("cargo:rustc-cfg=radium_atomic_polyfill_16");
+    }
+    if !atomics.has_32 {
+        atomics.has_32 = true;
+        println!("cargo:rustc-cfg=radium_atomic_polyfill_32");
+    }
+    if !atomics.has_64 {
+        atomics.has_64 = true;
+        println!("cargo:rustc-cfg=radium_atomic_polyfill_64");
+    }
+    if !atomics.has_ptr {
+        atomics.has_ptr = true;
+        println!("cargo:rustc-cfg=radium_atomic_polyfill_ptr");
+    }
+    if !atomics.cas_8 {
+        atomics.cas_8 = true;
+        println!("cargo:rustc-cfg=radium_atomic_polyfill_cas_8");
+    }
+    if !atomics.cas_16 {
+        atomics.cas_16 = true;
+        println!("cargo:rustc-cfg=radium_atomic_polyfill_cas_16");
+    }
+    if !atomics.cas_32 {
+        atomics.cas_32 = true;
+        println!("cargo:rustc-cfg=radium_atomic_polyfill_cas_32");
+    }
+    if !atomics.cas_64 {
+        atomics.cas_64 = true;
+        println!("cargo:rustc-cfg=radium_atomic_polyfill_cas_64");
+    }
+    if !atomics.cas_ptr {
+        atomics.cas_ptr = true;
+        println!("cargo:rustc-cfg=radium_atomic_polyfill_cas_ptr");
+    }
+
+    // Sub-features selecting which privilege level/interrupt line the
+    // critical section masks, mirroring portable-atomic's `s-mode` (RISC-V
+    // supervisor mode, instead of the machine-mode default) and `disable-fiq`
+    // (ARM, also mask FIQ rather than only IRQ) options.
+    if std::env::var_os("CARGO_FEATURE_S_MODE").is_some() {
+        println!("cargo:rustc-cfg=radium_s_mode");
+    }
+    if std::env::var_os("CARGO_FEATURE_DISABLE_FIQ").is_some() {
+        println!("cargo:rustc-cfg=radium_disable_fiq");
+    }
+}
+
+/// When the `portable-atomic` feature is enabled, every width and CAS flag is
+/// reported as present: `portable_atomic::AtomicU64` and friends emulate
+/// whatever the target's hardware cannot do natively, so no
+/// `radium_missing_*` cfg is emitted. The `target_arch` exclusions in the `64`
+/// arms of `has_atomic!`/`has_atomic_cas!` are unaffected (see the module
+/// docs).
+///
+/// Unlike [`apply_single_core_polyfill`], this does not need to record which
+/// widths it filled in — under this feature every Radium newtype wraps a
+/// `portable_atomic` type unconditionally rather than choosing between a
+/// `core::sync::atomic` type and a polyfill per width.
+fn apply_portable_atomic_override(atomics: &mut Atomics) {
+    if std::env::var_os("CARGO_FEATURE_PORTABLE_ATOMIC").is_some() {
+        *atomics = Atomics::ALL;
+    }
+}
@@ -0,0 +1,144 @@
+//! The pure target-to-[`Atomics`] decision table used by `build.rs`.
+//!
+//! This is its own module, included by `build.rs` via `mod detect;`, so that
+//! `tests/detect.rs` can also include it (via `#[path = "../detect.rs"]`) and
+//! actually exercise it under `cargo test`. A `#[cfg(test)]` module inside
+//! `build.rs` itself is never run by `cargo test` — Cargo compiles build
+//! scripts as `build-script-build`, not as a `--test` target — so the tests
+//! for this table live in `tests/detect.rs`.
+
+/// Collection of flags indicating whether the target processor supports atomic
+/// instructions for a certain width, and, separately, whether it supports the
+/// compare-and-swap (or equivalent read-modify-write) instruction needed to
+/// build `fetch_*`/`compare_exchange` on top of that width.
+///
+/// These are two different axes: `rustc`'s own `target_has_atomic` cfg is
+/// coarser than this and does not distinguish them, but targets such as
+/// ARMv6-M and MSP430 can load and store a width atomically while lacking the
+/// CAS instruction a read-modify-write loop needs.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Atomics {
+    /// Target supports 8-bit atomics
+    pub has_8: bool,
+    /// Target supports 16-bit atomics
+    pub has_16: bool,
+    /// Target supports 32-bit atomics
+    pub has_32: bool,
+    /// Target supports 64-bit atomics
+    pub has_64: bool,
+    /// Target supports word-width atomics
+    pub has_ptr: bool,
+    /// Target supports 8-bit compare-and-swap
+    pub cas_8: bool,
+    /// Target supports 16-bit compare-and-swap
+    pub cas_16: bool,
+    /// Target supports 32-bit compare-and-swap
+    pub cas_32: bool,
+    /// Target supports 64-bit compare-and-swap
+    pub cas_64: bool,
+    /// Target supports word-width compare-and-swap
+    pub cas_ptr: bool,
+}
+
+impl Atomics {
+    pub const ALL: Self = Self {
+        has_8: true,
+        has_16: true,
+        has_32: true,
+        has_64: true,
+        has_ptr: true,
+        cas_8: true,
+        cas_16: true,
+        cas_32: true,
+        cas_64: true,
+        cas_ptr: true,
+    };
+    pub const NONE: Self = Self {
+        has_8: false,
+        has_16: false,
+        has_32: false,
+        has_64: false,
+        has_ptr: false,
+        cas_8: false,
+        cas_16: false,
+        cas_32: false,
+        cas_64: false,
+        cas_ptr: false,
+    };
+}
+
+/// The pure target-to-`Atomics` decision table: pre-stabilization width
+/// detection, kept as a fallback for compilers older than 1.60 and for
+/// targets that `cfg(target_has_atomic)` cannot distinguish on its own, with
+/// the CAS table applied on top. Being a pure function of `target`/`arch`
+/// (no environment access), this is the part of detection `tests/detect.rs`
+/// covers.
+pub fn detect(target: &str, arch: &str) -> Atomics {
+    let mut atomics = Atomics::ALL;
+
+    // Add new target strings here with their atomic availability.
+    #[allow(clippy::match_single_binding, clippy::single_match)]
+    match target {
+        _ => {}
+    }
+
+    // Add new architecture sections here with their atomic availability.
+    #[allow(clippy::match_single_binding, clippy::single_match)]
+    match arch {
+        // "riscv32imc-unknown-none-elf" and "riscv32imac-unknown-none-elf" are
+        // both `target_arch = "riscv32", and have no `cfg`-discoverable
+        // distinction. As such, the non-atomic RISC-V target must be discovered
+        // here, rather than in the macro.
+        "riscv32i" | "riscv32imc" => atomics = Atomics::NONE,
+        "riscv32imac" => atomics.has_64 = false,
+        _ => {}
+    }
+
+    assume_cas_follows_width(&mut atomics);
+    apply_cas_overrides(&mut atomics, target, arch);
+    atomics
+}
+
+/// Assumes CAS is available wherever the width itself is; callers then narrow
+/// that down by hand, via [`apply_cas_overrides`], for the targets known to
+/// lack it. Only [`detect`], the fallback path, uses this; `build.rs`'s
+/// `detect_widths_from_cfg` reads CAS support off `target_has_atomic` instead.
+pub fn assume_cas_follows_width(atomics: &mut Atomics) {
+    atomics.cas_8 = atomics.has_8;
+    atomics.cas_16 = atomics.has_16;
+    atomics.cas_32 = atomics.has_32;
+    atomics.cas_64 = atomics.has_64;
+    atomics.cas_ptr = atomics.has_ptr;
+}
+
+/// Narrows `atomics`'s CAS flags for targets known to support atomic
+/// load/store for a width without supporting compare-and-swap on it. This
+/// table is consulted on every compiler version: by [`detect`], and by
+/// `build.rs`'s `detect_widths_from_cfg` after it reads `target_has_atomic`.
+pub fn apply_cas_overrides(atomics: &mut Atomics, target: &str, arch: &str) {
+    // ARMv6-M (Cortex-M0/M0+) and MSP430 can perform atomic load/store for
+    // these widths, but have no LDREX/STREX-equivalent instruction to build a
+    // compare-and-swap loop on top of them.
+    match target {
+        "thumbv6m-none-eabi" | "msp430-none-elf" => {
+            atomics.cas_8 = false;
+            atomics.cas_16 = false;
+            atomics.cas_32 = false;
+            atomics.cas_64 = false;
+            atomics.cas_ptr = false;
+        }
+        _ => {}
+    }
+
+    match arch {
+        "riscv32i" | "riscv32imc" => {
+            atomics.cas_8 = false;
+            atomics.cas_16 = false;
+            atomics.cas_32 = false;
+            atomics.cas_64 = false;
+            atomics.cas_ptr = false;
+        }
+        "riscv32imac" => atomics.cas_64 = false,
+        _ => {}
+    }
+}
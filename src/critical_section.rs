@@ -0,0 +1,380 @@
+//! Critical-section emulation for targets that lack a hardware atomic
+//! instruction for some width or operation.
+//!
+//! This module only exists when the crate is built with the
+//! `unsafe-assume-single-core` feature. Under it, `build.rs` marks every width
+//! whose atomic load/store or compare-and-swap the target lacks as
+//! polyfilled; this module performs those widths' operations — plain load and
+//! store as well as `fetch_*`, `compare_exchange`, and friends — on targets
+//! such as ARMv6-M and MSP430 that have no `LDREX`/`STREX` equivalent to build
+//! a CAS loop on, by disabling interrupts for the duration of the operation
+//! instead. [`acquire`]/[`release`] are only implemented for M-profile ARM
+//! (Cortex-M), MSP430, and 32-bit RISC-V; enabling the feature for any
+//! other architecture is a compile error, by design — there is no general
+//! "disable interrupts" instruction to fall back on.
+//!
+//! # Safety
+//!
+//! [`critical_section`] provides exclusion against concurrent access from
+//! other interrupt handlers and tasks *on the same core*; it does nothing to
+//! exclude another core. Enabling `unsafe-assume-single-core` on a
+//! multi-core target, or on a target where interrupts can still observe a
+//! partially-completed operation (e.g. a higher-priority interrupt that
+//! cannot be masked), is unsound. The feature name says as much: the caller
+//! is asserting, not radium, that the target is single-core.
+
+/// Runs `f` with interrupts disabled on the current core, restoring the prior
+/// interrupt-enable state on exit.
+///
+/// This is the building block every width's read-modify-write polyfill in
+/// this crate is implemented on top of. It is deliberately tiny: the critical
+/// section only needs to be held across a handful of instructions (a load, the
+/// caller's computation, and a store), not across arbitrary user code.
+#[inline]
+pub(crate) fn critical_section<R>(f: impl FnOnce() -> R) -> R {
+    let token = unsafe { acquire() };
+    let result = f();
+    unsafe { release(token) };
+    result
+}
+
+/// Disables interrupts on the current core and returns an opaque token
+/// capturing whether they were enabled beforehand, so [`release`] can restore
+/// the original state rather than unconditionally re-enabling them.
+///
+/// # Safety
+///
+/// Must be paired with exactly one call to [`release`] with the returned
+/// token, with no intervening call to [`acquire`] on the same core.
+///
+/// This is the ARMv7-M-and-later variant: the `if` field of the `CPS`
+/// encoding (disabling both IRQ and FIQ, selected by the `disable-fiq`
+/// sub-feature) is only valid from v7-M onward. ARMv6-M (Cortex-M0/M0+) only
+/// accepts the `i` field; see the variant below, gated on `not(target_feature
+/// = "v7")`, which is used there regardless of `disable-fiq`.
+#[cfg(all(target_arch = "arm", target_feature = "mclass", target_feature = "v7"))]
+#[inline]
+unsafe fn acquire() -> usize {
+    let primask: u32;
+    core::arch::asm!("mrs {}, PRIMASK", out(reg) primask);
+    // The `disable-fiq` sub-feature additionally masks FIQ, for targets whose
+    // FIQ handler can also touch a Radium-guarded value; plain `cpsid i` only
+    // masks IRQ.
+    if cfg!(radium_disable_fiq) {
+        core::arch::asm!("cpsid if");
+    } else {
+        core::arch::asm!("cpsid i");
+    }
+    primask as usize
+}
+
+/// ARMv6-M (Cortex-M0/M0+) variant of the [`acquire`] above. The v6-M `CPS`
+/// encoding only accepts the `i` field, so `disable-fiq` has no effect here —
+/// there is no narrower or wider critical section to ask for on this core.
+#[cfg(all(
+    target_arch = "arm",
+    target_feature = "mclass",
+    not(target_feature = "v7")
+))]
+#[inline]
+unsafe fn acquire() -> usize {
+    let primask: u32;
+    core::arch::asm!("mrs {}, PRIMASK", out(reg) primask);
+    core::arch::asm!("cpsid i");
+    primask as usize
+}
+
+/// See the ARMv6-M [`acquire`] above; this is the MSP430 equivalent, saving
+/// and then clearing the status register's global interrupt-enable bit.
+#[cfg(target_arch = "msp430")]
+#[inline]
+unsafe fn acquire() -> usize {
+    let sr: u16;
+    core::arch::asm!("mov R2, {}", out(reg) sr);
+    core::arch::asm!("dint");
+    sr as usize
+}
+
+/// See the ARMv6-M [`acquire`] above; this is the RISC-V equivalent. Masks the
+/// global interrupt-enable bit of `mstatus` (machine mode, the default) or
+/// `sstatus` (when the `s-mode` sub-feature selects supervisor mode).
+#[cfg(target_arch = "riscv32")]
+#[inline]
+unsafe fn acquire() -> usize {
+    let mask: usize;
+    if cfg!(radium_s_mode) {
+        core::arch::asm!("csrrci {}, sstatus, 0b10", out(reg) mask);
+    } else {
+        core::arch::asm!("csrrci {}, mstatus, 0b1000", out(reg) mask);
+    }
+    mask
+}
+
+/// Restores the interrupt-enable state captured by [`acquire`].
+///
+/// # Safety
+///
+/// `token` must be the value [`acquire`] most recently returned on this core,
+/// with no other call to [`acquire`] or [`release`] in between.
+///
+/// ARMv7-M-and-later variant; see the [`acquire`] doc above for why this is
+/// split from the ARMv6-M variant below.
+#[cfg(all(target_arch = "arm", target_feature = "mclass", target_feature = "v7"))]
+#[inline]
+unsafe fn release(token: usize) {
+    if token & 1 == 0 {
+        if cfg!(radium_disable_fiq) {
+            core::arch::asm!("cpsie if");
+        } else {
+            core::arch::asm!("cpsie i");
+        }
+    }
+}
+
+/// ARMv6-M (Cortex-M0/M0+) variant of the [`release`] above.
+#[cfg(all(
+    target_arch = "arm",
+    target_feature = "mclass",
+    not(target_feature = "v7")
+))]
+#[inline]
+unsafe fn release(token: usize) {
+    if token & 1 == 0 {
+        core::arch::asm!("cpsie i");
+    }
+}
+
+#[cfg(target_arch = "msp430")]
+#[inline]
+unsafe fn release(token: usize) {
+    if token & (1 << 3) != 0 {
+        core::arch::asm!("eint");
+    }
+}
+
+#[cfg(target_arch = "riscv32")]
+#[inline]
+unsafe fn release(token: usize) {
+    if cfg!(radium_s_mode) {
+        if token & 0b10 != 0 {
+            core::arch::asm!("csrsi sstatus, 0b10");
+        }
+    } else if token & 0b1000 != 0 {
+        core::arch::asm!("csrsi mstatus, 0b1000");
+    }
+}
+
+/// Generates the read-modify-write operations a width-specific atomic type
+/// needs, implemented by reading, modifying, and writing back through a raw
+/// pointer inside a single [`critical_section`] rather than with a hardware
+/// CAS instruction.
+///
+/// # Safety
+///
+/// Every function generated by this macro requires `dst` to be valid for
+/// reads and writes for the duration of the call, and requires that all other
+/// accesses to `*dst` (on this core) also go through one of these functions,
+/// [`critical_section`], or a plain load/store — never a hardware
+/// read-modify-write instruction the target doesn't actually have.
+macro_rules! rmw_ops {
+    ($t:ty => $load:ident, $store:ident, $fetch_add:ident, $fetch_sub:ident, $fetch_and:ident, $fetch_or:ident, $fetch_xor:ident, $swap:ident, $compare_exchange:ident) => {
+        #[doc = concat!("`", stringify!($t), "::load`, emulated with a critical section.")]
+        pub unsafe fn $load(dst: *mut $t) -> $t {
+            critical_section(|| core::ptr::read(dst))
+        }
+
+        #[doc = concat!("`", stringify!($t), "::store`, emulated with a critical section.")]
+        pub unsafe fn $store(dst: *mut $t, val: $t) {
+            critical_section(|| core::ptr::write(dst, val))
+        }
+
+        #[doc = concat!("`", stringify!($t), "::fetch_add`, emulated with a critical section.")]
+        pub unsafe fn $fetch_add(dst: *mut $t, val: $t) -> $t {
+            critical_section(|| {
+                let old = core::ptr::read(dst);
+                core::ptr::write(dst, old.wrapping_add(val));
+                old
+            })
+        }
+
+        #[doc = concat!("`", stringify!($t), "::fetch_sub`, emulated with a critical section.")]
+        pub unsafe fn $fetch_sub(dst: *mut $t, val: $t) -> $t {
+            critical_section(|| {
+                let old = core::ptr::read(dst);
+                core::ptr::write(dst, old.wrapping_sub(val));
+                old
+            })
+        }
+
+        #[doc = concat!("`", stringify!($t), "::fetch_and`, emulated with a critical section.")]
+        pub unsafe fn $fetch_and(dst: *mut $t, val: $t) -> $t {
+            critical_section(|| {
+                let old = core::ptr::read(dst);
+                core::ptr::write(dst, old & val);
+                old
+            })
+        }
+
+        #[doc = concat!("`", stringify!($t), "::fetch_or`, emulated with a critical section.")]
+        pub unsafe fn $fetch_or(dst: *mut $t, val: $t) -> $t {
+            critical_section(|| {
+                let old = core::ptr::read(dst);
+                core::ptr::write(dst, old | val);
+                old
+            })
+        }
+
+        #[doc = concat!("`", stringify!($t), "::fetch_xor`, emulated with a critical section.")]
+        pub unsafe fn $fetch_xor(dst: *mut $t, val: $t) -> $t {
+            critical_section(|| {
+                let old = core::ptr::read(dst);
+                core::ptr::write(dst, old ^ val);
+                old
+            })
+        }
+
+        #[doc = concat!("`", stringify!($t), "::swap`, emulated with a critical section.")]
+        pub unsafe fn $swap(dst: *mut $t, val: $t) -> $t {
+            critical_section(|| {
+                let old = core::ptr::read(dst);
+                core::ptr::write(dst, val);
+                old
+            })
+        }
+
+        #[doc = concat!("`", stringify!($t), "::compare_exchange`, emulated with a critical section.")]
+        pub unsafe fn $compare_exchange(dst: *mut $t, current: $t, new: $t) -> Result<$t, $t> {
+            critical_section(|| {
+                let old = core::ptr::read(dst);
+                if old == current {
+                    core::ptr::write(dst, new);
+                    Ok(old)
+                } else {
+                    Err(old)
+                }
+            })
+        }
+    };
+}
+
+rmw_ops!(u8 => load_8, store_8, fetch_add_8, fetch_sub_8, fetch_and_8, fetch_or_8, fetch_xor_8, swap_8, compare_exchange_8);
+rmw_ops!(u16 => load_16, store_16, fetch_add_16, fetch_sub_16, fetch_and_16, fetch_or_16, fetch_xor_16, swap_16, compare_exchange_16);
+rmw_ops!(u32 => load_32, store_32, fetch_add_32, fetch_sub_32, fetch_and_32, fetch_or_32, fetch_xor_32, swap_32, compare_exchange_32);
+rmw_ops!(u64 => load_64, store_64, fetch_add_64, fetch_sub_64, fetch_and_64, fetch_or_64, fetch_xor_64, swap_64, compare_exchange_64);
+rmw_ops!(usize => load_ptr, store_ptr, fetch_add_ptr, fetch_sub_ptr, fetch_and_ptr, fetch_or_ptr, fetch_xor_ptr, swap_ptr, compare_exchange_ptr);
+
+/// Backs [`Radium`](crate::Radium) for a width that has no native
+/// compare-and-swap (and possibly no native atomic load/store either), on
+/// targets where
+/// `unsafe-assume-single-core` fills that gap in with this module's
+/// critical section rather than a hardware read-modify-write instruction;
+/// see the `radium_atomic_polyfill_cas_*` cfgs `build.rs`'s
+/// `apply_single_core_polyfill` emits.
+///
+/// Unlike the `core::sync::atomic` types `src/types.rs` wraps directly,
+/// every operation here — including plain load/store — goes through
+/// [`critical_section`], rather than assuming the target's native load/store
+/// instruction is itself available to build on; this keeps the type correct
+/// on its own, independent of exactly which methods the compiler happens to
+/// expose on the native atomic type for this target.
+pub struct AtomicPolyfill<T>(core::cell::UnsafeCell<T>);
+
+// SAFETY: every access to the wrapped `T` goes through `critical_section`,
+// which excludes concurrent access from other interrupt handlers and tasks
+// on this core; see the module safety docs for why this does not extend to
+// other cores.
+unsafe impl<T: Send> Sync for AtomicPolyfill<T> {}
+
+/// Implements [`Radium`](crate::Radium) for [`AtomicPolyfill<$prim>`] by
+/// forwarding to the free functions [`rmw_ops!`] generated for `$prim`,
+/// gated on the `radium_atomic_polyfill_cas_$width` cfg that marks this
+/// width as CAS-polyfilled rather than natively CAS-capable.
+macro_rules! impl_radium_polyfill {
+    (8: $prim:ty, $load:ident, $store:ident, $fetch_add:ident, $fetch_sub:ident, $fetch_and:ident, $fetch_or:ident, $fetch_xor:ident, $swap:ident, $compare_exchange:ident) => {
+        #[cfg(radium_atomic_polyfill_cas_8)]
+        impl_radium_polyfill!(@impl $prim, $load, $store, $fetch_add, $fetch_sub, $fetch_and, $fetch_or, $fetch_xor, $swap, $compare_exchange);
+    };
+    (16: $prim:ty, $load:ident, $store:ident, $fetch_add:ident, $fetch_sub:ident, $fetch_and:ident, $fetch_or:ident, $fetch_xor:ident, $swap:ident, $compare_exchange:ident) => {
+        #[cfg(radium_atomic_polyfill_cas_16)]
+        impl_radium_polyfill!(@impl $prim, $load, $store, $fetch_add, $fetch_sub, $fetch_and, $fetch_or, $fetch_xor, $swap, $compare_exchange);
+    };
+    (32: $prim:ty, $load:ident, $store:ident, $fetch_add:ident, $fetch_sub:ident, $fetch_and:ident, $fetch_or:ident, $fetch_xor:ident, $swap:ident, $compare_exchange:ident) => {
+        #[cfg(radium_atomic_polyfill_cas_32)]
+        impl_radium_polyfill!(@impl $prim, $load, $store, $fetch_add, $fetch_sub, $fetch_and, $fetch_or, $fetch_xor, $swap, $compare_exchange);
+    };
+    (64: $prim:ty, $load:ident, $store:ident, $fetch_add:ident, $fetch_sub:ident, $fetch_and:ident, $fetch_or:ident, $fetch_xor:ident, $swap:ident, $compare_exchange:ident) => {
+        #[cfg(radium_atomic_polyfill_cas_64)]
+        impl_radium_polyfill!(@impl $prim, $load, $store, $fetch_add, $fetch_sub, $fetch_and, $fetch_or, $fetch_xor, $swap, $compare_exchange);
+    };
+    (ptr: $prim:ty, $load:ident, $store:ident, $fetch_add:ident, $fetch_sub:ident, $fetch_and:ident, $fetch_or:ident, $fetch_xor:ident, $swap:ident, $compare_exchange:ident) => {
+        #[cfg(radium_atomic_polyfill_cas_ptr)]
+        impl_radium_polyfill!(@impl $prim, $load, $store, $fetch_add, $fetch_sub, $fetch_and, $fetch_or, $fetch_xor, $swap, $compare_exchange);
+    };
+    (@impl $prim:ty, $load:ident, $store:ident, $fetch_add:ident, $fetch_sub:ident, $fetch_and:ident, $fetch_or:ident, $fetch_xor:ident, $swap:ident, $compare_exchange:ident) => {
+        impl crate::Radium for AtomicPolyfill<$prim> {
+            type Item = $prim;
+
+            #[inline]
+            fn new(value: Self::Item) -> Self {
+                Self(core::cell::UnsafeCell::new(value))
+            }
+
+            #[inline]
+            fn load(&self, _order: core::sync::atomic::Ordering) -> Self::Item {
+                unsafe { $load(self.0.get()) }
+            }
+
+            #[inline]
+            fn store(&self, value: Self::Item, _order: core::sync::atomic::Ordering) {
+                unsafe { $store(self.0.get(), value) }
+            }
+
+            #[inline]
+            fn swap(&self, value: Self::Item, _order: core::sync::atomic::Ordering) -> Self::Item {
+                unsafe { $swap(self.0.get(), value) }
+            }
+
+            #[inline]
+            fn compare_exchange(
+                &self,
+                current: Self::Item,
+                new: Self::Item,
+                _success: core::sync::atomic::Ordering,
+                _failure: core::sync::atomic::Ordering,
+            ) -> Result<Self::Item, Self::Item> {
+                unsafe { $compare_exchange(self.0.get(), current, new) }
+            }
+
+            #[inline]
+            fn fetch_add(&self, val: Self::Item, _order: core::sync::atomic::Ordering) -> Self::Item {
+                unsafe { $fetch_add(self.0.get(), val) }
+            }
+
+            #[inline]
+            fn fetch_sub(&self, val: Self::Item, _order: core::sync::atomic::Ordering) -> Self::Item {
+                unsafe { $fetch_sub(self.0.get(), val) }
+            }
+
+            #[inline]
+            fn fetch_and(&self, val: Self::Item, _order: core::sync::atomic::Ordering) -> Self::Item {
+                unsafe { $fetch_and(self.0.get(), val) }
+            }
+
+            #[inline]
+            fn fetch_or(&self, val: Self::Item, _order: core::sync::atomic::Ordering) -> Self::Item {
+                unsafe { $fetch_or(self.0.get(), val) }
+            }
+
+            #[inline]
+            fn fetch_xor(&self, val: Self::Item, _order: core::sync::atomic::Ordering) -> Self::Item {
+                unsafe { $fetch_xor(self.0.get(), val) }
+            }
+        }
+    };
+}
+
+impl_radium_polyfill!(8: u8, load_8, store_8, fetch_add_8, fetch_sub_8, fetch_and_8, fetch_or_8, fetch_xor_8, swap_8, compare_exchange_8);
+impl_radium_polyfill!(16: u16, load_16, store_16, fetch_add_16, fetch_sub_16, fetch_and_16, fetch_or_16, fetch_xor_16, swap_16, compare_exchange_16);
+impl_radium_polyfill!(32: u32, load_32, store_32, fetch_add_32, fetch_sub_32, fetch_and_32, fetch_or_32, fetch_xor_32, swap_32, compare_exchange_32);
+impl_radium_polyfill!(64: u64, load_64, store_64, fetch_add_64, fetch_sub_64, fetch_and_64, fetch_or_64, fetch_xor_64, swap_64, compare_exchange_64);
+impl_radium_polyfill!(ptr: usize, load_ptr, store_ptr, fetch_add_ptr, fetch_sub_ptr, fetch_and_ptr, fetch_or_ptr, fetch_xor_ptr, swap_ptr, compare_exchange_ptr);
@@ -0,0 +1,19 @@
+//! Portable, fallback-free, generic-over-atomicity.
+//!
+//! `radium` lets crates write code once against a `cfg`-selected atomic type,
+//! rather than hand-rolling `cfg(target_has_atomic)` gates at every call site.
+//! See `build.rs` for how that selection is made, [`has_atomic!`] /
+//! [`has_atomic_cas!`] for how a dependent crate consumes it directly, and
+//! [`Radium`] for the trait form of the same selection.
+#![cfg_attr(not(test), no_std)]
+
+mod has_atomic;
+mod types;
+
+#[cfg(feature = "unsafe-assume-single-core")]
+mod critical_section;
+
+pub use crate::types::Radium;
+
+#[cfg(feature = "unsafe-assume-single-core")]
+pub use crate::critical_section::AtomicPolyfill;
@@ -0,0 +1,172 @@
+//! The `Radium` trait: a single interface over a per-width atomic type, so a
+//! dependent crate can write `T: Radium` once instead of hand-picking
+//! `core::sync::atomic::AtomicU32` vs. a polyfill at every call site.
+//!
+//! Each width for which [`has_atomic_cas!`] holds gets a `Radium`
+//! implementation. By default that wraps the matching `core::sync::atomic`
+//! type; under the `portable-atomic` feature it wraps the matching
+//! [`portable_atomic`] type instead, so a width `portable_atomic` emulates
+//! (because `build.rs` reports it present under that feature, regardless of
+//! native hardware support) gets a `Radium` impl too. The exception is 64-bit
+//! on the targets the `64` arm of [`has_atomic_cas!`] excludes by
+//! `target_arch` (32-bit ARM Android, MIPS, PowerPC), which get no 64-bit
+//! impl under either backend. Either way the dependent crate's code is
+//! unchanged — it only ever names `Radium`.
+//!
+//! [`has_atomic_cas!`]: crate::has_atomic_cas
+
+use core::sync::atomic::Ordering;
+
+#[cfg(feature = "portable-atomic")]
+use portable_atomic::{AtomicU16, AtomicU32, AtomicU64, AtomicU8, AtomicUsize};
+
+#[cfg(not(feature = "portable-atomic"))]
+use core::sync::atomic::{AtomicU16, AtomicU32, AtomicU64, AtomicU8, AtomicUsize};
+
+/// A type that provides atomic load/store and read-modify-write operations
+/// over some [`Item`](Radium::Item).
+///
+/// This mirrors the subset of `core::sync::atomic::AtomicN` inherent methods
+/// that every width radium supports needs; it exists so that code can be
+/// generic over "some atomic integer of this width" rather than over a
+/// specific backing type.
+pub trait Radium {
+    /// The scalar value this atomic type holds.
+    type Item;
+
+    /// Wraps `value` in a new atomic cell.
+    fn new(value: Self::Item) -> Self;
+
+    /// Loads the current value.
+    fn load(&self, order: Ordering) -> Self::Item;
+
+    /// Stores `value`, discarding whatever was previously held.
+    fn store(&self, value: Self::Item, order: Ordering);
+
+    /// Stores `value`, returning the previously-held value.
+    fn swap(&self, value: Self::Item, order: Ordering) -> Self::Item;
+
+    /// Stores `new` if the current value equals `current`, otherwise leaves
+    /// it unchanged; returns the previously-held value either way.
+    fn compare_exchange(
+        &self,
+        current: Self::Item,
+        new: Self::Item,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Self::Item, Self::Item>;
+
+    /// Adds `val` to the current value, returning the previously-held value.
+    fn fetch_add(&self, val: Self::Item, order: Ordering) -> Self::Item;
+
+    /// Subtracts `val` from the current value, returning the previously-held
+    /// value.
+    fn fetch_sub(&self, val: Self::Item, order: Ordering) -> Self::Item;
+
+    /// Bitwise-ANDs `val` into the current value, returning the
+    /// previously-held value.
+    fn fetch_and(&self, val: Self::Item, order: Ordering) -> Self::Item;
+
+    /// Bitwise-ORs `val` into the current value, returning the
+    /// previously-held value.
+    fn fetch_or(&self, val: Self::Item, order: Ordering) -> Self::Item;
+
+    /// Bitwise-XORs `val` into the current value, returning the
+    /// previously-held value.
+    fn fetch_xor(&self, val: Self::Item, order: Ordering) -> Self::Item;
+}
+
+/// Implements [`Radium`] for `$atomic` by forwarding every method to the
+/// identically-named inherent method on `$atomic` itself, gated on `$width`
+/// having both load/store and compare-and-swap support.
+///
+/// `$atomic` is whichever type the surrounding `use` brought into scope for
+/// that width: `core::sync::atomic::$atomic` ordinarily, or
+/// `portable_atomic::$atomic` under the `portable-atomic` feature.
+///
+/// `$polyfill` is the `radium_atomic_polyfill_cas_$width` cfg `build.rs`
+/// emits when `unsafe-assume-single-core` fills this width's CAS gap in with
+/// a critical section rather than a hardware instruction (see
+/// `src/critical_section.rs::AtomicPolyfill`). When that cfg holds and
+/// `portable-atomic` is off, `$atomic` names `core::sync::atomic::$atomic`,
+/// which on such a target does not even expose the methods below — so this
+/// impl must not apply there; `AtomicPolyfill` is used instead. Under
+/// `portable-atomic`, `$atomic` already names the fully-capable
+/// `portable_atomic` type regardless of `$polyfill`, so the exclusion is
+/// lifted.
+macro_rules! impl_radium {
+    ($([$prim:ty, $atomic:ty, $width:tt, $polyfill:meta]),+ $(,)?) => {
+        $(
+            #[cfg(not(all($polyfill, not(feature = "portable-atomic"))))]
+            $crate::has_atomic_cas!($width
+                impl Radium for $atomic {
+                    type Item = $prim;
+
+                    #[inline]
+                    fn new(value: Self::Item) -> Self {
+                        <$atomic>::new(value)
+                    }
+
+                    #[inline]
+                    fn load(&self, order: Ordering) -> Self::Item {
+                        <$atomic>::load(self, order)
+                    }
+
+                    #[inline]
+                    fn store(&self, value: Self::Item, order: Ordering) {
+                        <$atomic>::store(self, value, order)
+                    }
+
+                    #[inline]
+                    fn swap(&self, value: Self::Item, order: Ordering) -> Self::Item {
+                        <$atomic>::swap(self, value, order)
+                    }
+
+                    #[inline]
+                    fn compare_exchange(
+                        &self,
+                        current: Self::Item,
+                        new: Self::Item,
+                        success: Ordering,
+                        failure: Ordering,
+                    ) -> Result<Self::Item, Self::Item> {
+                        <$atomic>::compare_exchange(self, current, new, success, failure)
+                    }
+
+                    #[inline]
+                    fn fetch_add(&self, val: Self::Item, order: Ordering) -> Self::Item {
+                        <$atomic>::fetch_add(self, val, order)
+                    }
+
+                    #[inline]
+                    fn fetch_sub(&self, val: Self::Item, order: Ordering) -> Self::Item {
+                        <$atomic>::fetch_sub(self, val, order)
+                    }
+
+                    #[inline]
+                    fn fetch_and(&self, val: Self::Item, order: Ordering) -> Self::Item {
+                        <$atomic>::fetch_and(self, val, order)
+                    }
+
+                    #[inline]
+                    fn fetch_or(&self, val: Self::Item, order: Ordering) -> Self::Item {
+                        <$atomic>::fetch_or(self, val, order)
+                    }
+
+                    #[inline]
+                    fn fetch_xor(&self, val: Self::Item, order: Ordering) -> Self::Item {
+                        <$atomic>::fetch_xor(self, val, order)
+                    }
+                }
+            );
+        )+
+    };
+}
+
+impl_radium!(
+    [u8, AtomicU8, 8, radium_atomic_polyfill_cas_8],
+    [u16, AtomicU16, 16, radium_atomic_polyfill_cas_16],
+    [u32, AtomicU32, 32, radium_atomic_polyfill_cas_32],
+    [u64, AtomicU64, 64, radium_atomic_polyfill_cas_64],
+    [usize, AtomicUsize, ptr, radium_atomic_polyfill_cas_ptr],
+);
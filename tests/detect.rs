@@ -0,0 +1,50 @@
+//! Exercises `detect.rs`'s pure target-to-`Atomics` decision table.
+//!
+//! A `#[cfg(test)] mod tests` inside `build.rs` itself would never be compiled
+//! by `cargo test`: Cargo builds a build script as `build-script-build`, not
+//! as a `--test` target. Including `detect.rs` here via `#[path]` gives the
+//! table real coverage.
+
+#[path = "../detect.rs"]
+mod detect;
+
+use detect::Atomics;
+
+fn arch_of(target: &str) -> &str {
+    target.split('-').next().unwrap()
+}
+
+#[test]
+fn ordinary_targets_have_full_atomics() {
+    let target = "x86_64-unknown-linux-gnu";
+    assert_eq!(detect::detect(target, arch_of(target)), Atomics::ALL);
+}
+
+#[test]
+fn riscv32_without_the_atomic_extension_has_no_atomics() {
+    for target in ["riscv32i-unknown-none-elf", "riscv32imc-unknown-none-elf"] {
+        assert_eq!(detect::detect(target, arch_of(target)), Atomics::NONE, "{}", target);
+    }
+}
+
+#[test]
+fn riscv32imac_is_missing_only_64_bit() {
+    let target = "riscv32imac-unknown-none-elf";
+    let atomics = detect::detect(target, arch_of(target));
+    assert!(!atomics.has_64);
+    assert!(!atomics.cas_64);
+    assert!(atomics.has_32 && atomics.cas_32);
+    assert!(atomics.has_ptr && atomics.cas_ptr);
+}
+
+#[test]
+fn armv6_m_and_msp430_have_atomics_but_no_cas() {
+    for target in ["thumbv6m-none-eabi", "msp430-none-elf"] {
+        let atomics = detect::detect(target, arch_of(target));
+        assert!(atomics.has_8 && atomics.has_16 && atomics.has_32, "{}", target);
+        assert!(
+            !atomics.cas_8 && !atomics.cas_16 && !atomics.cas_32 && !atomics.cas_ptr,
+            "{}", target
+        );
+    }
+}
@@ -0,0 +1,51 @@
+//! Minimal `rustc --version` parsing.
+//!
+//! `build.rs` needs to know whether the compiler it is running under is new
+//! enough to support `cfg(target_has_atomic)` (stabilized in Rust 1.60), so it
+//! can read `CARGO_CFG_TARGET_HAS_ATOMIC` instead of maintaining its own
+//! target tables. This mirrors the small ad hoc version probes build scripts
+//! such as `portable-atomic`'s use for the same purpose.
+
+use std::process::Command;
+
+/// A `rustc` version, truncated to the `major.minor` precision this build
+/// script cares about.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub struct RustcVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl RustcVersion {
+    /// The first stable release to carry `cfg(target_has_atomic)`.
+    pub const TARGET_HAS_ATOMIC: Self = Self { major: 1, minor: 60 };
+
+    /// Whether `self` is at least as new as `other`.
+    pub fn at_least(self, other: Self) -> bool {
+        (self.major, self.minor) >= (other.major, other.minor)
+    }
+}
+
+/// Runs `$RUSTC --version` (falling back to `rustc` if the env var is unset)
+/// and parses the reported version.
+///
+/// Returns `None` if the compiler could not be located or its output could
+/// not be parsed; callers should fall back to the pre-stabilization detection
+/// path in that case, since we cannot be sure `target_has_atomic` is present.
+pub fn detect() -> Option<RustcVersion> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc).arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse(&String::from_utf8(output.stdout).ok()?)
+}
+
+/// Parses a `rustc --version` string, e.g. `rustc 1.60.0 (7737e0b5c 2022-04-04)`.
+fn parse(text: &str) -> Option<RustcVersion> {
+    let version = text.split_whitespace().nth(1)?;
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some(RustcVersion { major, minor })
+}